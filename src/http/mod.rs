@@ -1,5 +1,14 @@
+use std::borrow::Cow;
 use std::str;
-use std::collections::HashMap; 
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression;
+
+pub mod router;
+pub mod websocket;
 
 
 #[derive(Debug, PartialEq)]
@@ -10,6 +19,58 @@ pub enum ParseError {
     InvalidVersion,
     InvalidHeader,
     TooManyHeaders,
+    InvalidChunk,
+}
+
+/// Everything that can go wrong while servicing a connection, beyond the
+/// incremental "not enough bytes yet" signal of `ParseError::Partial`
+/// (which drives the caller's read loop rather than ever becoming one of
+/// these). Replaces the ad-hoc `eprintln!` + hardcoded `bad_request()`
+/// that used to collapse every failure into a generic 400.
+#[derive(Debug)]
+pub enum ServerError {
+    /// The request was malformed in a way `parse_request` or
+    /// `HttpRequest::try_from` could detect.
+    Parse(ParseError),
+    /// An I/O error occurred while reading from or writing to the socket.
+    Io(io::Error),
+    /// The client didn't finish sending a request within the configured
+    /// read deadline.
+    Timeout,
+    /// The request exceeded the server's maximum buffered size.
+    TooLarge,
+}
+
+impl From<ParseError> for ServerError {
+    fn from(error: ParseError) -> Self {
+        ServerError::Parse(error)
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(error: io::Error) -> Self {
+        ServerError::Io(error)
+    }
+}
+
+/// Maps a fallible outcome onto the HTTP status response it should
+/// produce, so callers can turn any error into a predictable,
+/// status-accurate reply with a single call.
+pub trait ResponseError {
+    fn status_response(&self) -> Response;
+}
+
+impl ResponseError for ServerError {
+    fn status_response(&self) -> Response {
+        match self {
+            // Every `ParseError` variant (besides `Partial`, which never
+            // reaches here) represents a malformed request.
+            ServerError::Parse(_) => Response::bad_request(),
+            ServerError::TooLarge => Response::payload_too_large(),
+            ServerError::Timeout => Response::request_timeout(),
+            ServerError::Io(_) => Response::internal_server_error(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -24,7 +85,9 @@ pub struct Request<'buf, 'h> {
     pub path: &'buf str,
     pub version: &'buf str,
     pub headers: &'h [Header<'buf>],
-    pub body: &'buf [u8],
+    /// Borrowed straight out of `buffer` for `Content-Length` bodies;
+    /// owned when the body had to be reassembled from `chunked` framing.
+    pub body: Cow<'buf, [u8]>,
 }
 
 const MAX_HEADERS: usize = 32;
@@ -70,23 +133,30 @@ pub fn parse_request<'buf, 'h>(
     let parsed_headers = &headers[..header_count];
 
     let mut content_length = 0;
+    let mut is_chunked = false;
     for header in parsed_headers {
         if header.name.eq_ignore_ascii_case("Content-Length") {
             let value_str = str::from_utf8(header.value).map_err(|_| ParseError::InvalidHeader)?;
             content_length = value_str.parse::<usize>().map_err(|_| ParseError::InvalidHeader)?;
-            break;
+        } else if header.name.eq_ignore_ascii_case("Transfer-Encoding") {
+            let value_str = str::from_utf8(header.value).map_err(|_| ParseError::InvalidHeader)?;
+            is_chunked = value_str.trim().to_ascii_lowercase().ends_with("chunked");
         }
     }
 
     // The body starts exactly where the cursor was left after the header loop.
     let body_start = cursor;
-    let total_request_size = body_start + content_length;
 
-    if buffer.len() < total_request_size {
-        return Err(ParseError::Partial);
-    }
-
-    let body = &buffer[body_start..total_request_size];
+    let (body, total_request_size) = if is_chunked {
+        let (decoded, end) = decode_chunked_body(buffer, body_start)?;
+        (Cow::Owned(decoded), end)
+    } else {
+        let total_request_size = body_start + content_length;
+        if buffer.len() < total_request_size {
+            return Err(ParseError::Partial);
+        }
+        (Cow::Borrowed(&buffer[body_start..total_request_size]), total_request_size)
+    };
 
     let request = Request {
         method: str::from_utf8(method_bytes).map_err(|_| ParseError::InvalidMethod)?,
@@ -95,9 +165,55 @@ pub fn parse_request<'buf, 'h>(
         headers: parsed_headers,
         body,
     };
-    
+
     Ok((request, total_request_size))
 }
+
+/// Decodes a `Transfer-Encoding: chunked` body starting at `start` in
+/// `buffer`. Each chunk is a CRLF-terminated hex size line followed by
+/// exactly that many payload bytes and a CRLF, terminated by a zero-size
+/// chunk plus a final CRLF; trailers after the final chunk aren't
+/// supported. Returns the concatenated payload and the offset just past
+/// the terminating CRLF, so the caller's `total_request_size` still
+/// covers all of the chunk framing.
+fn decode_chunked_body(buffer: &[u8], start: usize) -> Result<(Vec<u8>, usize), ParseError> {
+    let mut cursor = start;
+    let mut decoded = Vec::new();
+
+    loop {
+        let line_end = find_crlf(&buffer[cursor..]).ok_or(ParseError::Partial)?;
+        let size_line = &buffer[cursor..cursor + line_end];
+        let size_bytes = size_line.split(|&b| b == b';').next().unwrap_or(size_line);
+        let size_str = str::from_utf8(size_bytes).map_err(|_| ParseError::InvalidChunk)?.trim();
+        let chunk_size = usize::from_str_radix(size_str, 16).map_err(|_| ParseError::InvalidChunk)?;
+        cursor += line_end + 2;
+
+        if chunk_size == 0 {
+            if buffer.len() < cursor + 2 {
+                return Err(ParseError::Partial);
+            }
+            if &buffer[cursor..cursor + 2] != b"\r\n" {
+                return Err(ParseError::InvalidChunk);
+            }
+            cursor += 2;
+            break;
+        }
+
+        if buffer.len() < cursor + chunk_size + 2 {
+            return Err(ParseError::Partial);
+        }
+
+        decoded.extend_from_slice(&buffer[cursor..cursor + chunk_size]);
+        cursor += chunk_size;
+
+        if &buffer[cursor..cursor + 2] != b"\r\n" {
+            return Err(ParseError::InvalidChunk);
+        }
+        cursor += 2;
+    }
+
+    Ok((decoded, cursor))
+}
 fn find_crlf(buffer: &[u8]) -> Option<usize> {
     buffer.windows(2).position(|window| window == b"\r\n")
 }
@@ -118,7 +234,7 @@ impl TrimStart for [u8] {
 
 // --- NEW: Application-Level (Owned) HTTP Types ---
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Method {
     Get,
     Post,
@@ -138,6 +254,10 @@ pub struct HttpRequest {
     pub version: String,
     pub headers: HashMap<String, Vec<u8>>,
     pub body: Vec<u8>,
+    /// Path parameters captured by the `Router`, e.g. `:id` in
+    /// `/api/users/:id`. Empty until a route matching the request's path
+    /// has been found.
+    pub params: HashMap<String, String>,
 }
 
 impl<'buf, 'h> TryFrom<Request<'buf, 'h>> for HttpRequest {
@@ -168,6 +288,7 @@ impl<'buf, 'h> TryFrom<Request<'buf, 'h>> for HttpRequest {
             version: borrowed_req.version.to_string(),
             headers,
             body: borrowed_req.body.to_vec(),
+            params: HashMap::new(),
         })
     }
 }
@@ -244,6 +365,99 @@ impl<'buf, 'h> TryFrom<Request<'buf, 'h>> for HttpRequest {
 //     }
 // }
 
+/// Bodies shorter than this aren't worth compressing; the codec overhead
+/// can outweigh the savings for tiny responses.
+const MIN_COMPRESSION_LEN: usize = 256;
+
+/// The codecs we know how to produce, in descending preference order. Used
+/// both to recognize a token in `Accept-Encoding` and to break ties when
+/// two codecs share the same `q` value.
+const SUPPORTED_ENCODINGS: [&str; 3] = ["br", "gzip", "deflate"];
+
+/// Picks the best codec the client will accept, honoring `q` values: a
+/// codec explicitly marked `q=0` (the `Accept-Encoding` syntax for "not
+/// acceptable") is never chosen, the highest surviving `q` wins, and ties
+/// fall back to `SUPPORTED_ENCODINGS`'s br → gzip → deflate order.
+fn pick_encoding(accept_encoding: &str) -> Option<&'static str> {
+    let mut best: Option<(&'static str, f32)> = None;
+
+    for token in accept_encoding.split(',') {
+        let mut parts = token.split(';');
+        let name = parts.next().unwrap_or("").trim();
+
+        let Some(candidate) = SUPPORTED_ENCODINGS.iter().find(|&&c| name.eq_ignore_ascii_case(c)) else {
+            continue;
+        };
+
+        let q = parts
+            .find_map(|param| param.trim().strip_prefix("q=").and_then(|v| v.parse::<f32>().ok()))
+            .unwrap_or(1.0);
+
+        // `q=0` means the client explicitly refuses this codec.
+        if q <= 0.0 {
+            continue;
+        }
+
+        let rank = |c: &str| SUPPORTED_ENCODINGS.iter().position(|s| *s == c).unwrap_or(usize::MAX);
+        let is_better = match best {
+            None => true,
+            Some((best_candidate, best_q)) => q > best_q || (q == best_q && rank(candidate) < rank(best_candidate)),
+        };
+
+        if is_better {
+            best = Some((candidate, q));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate)
+}
+
+/// Configures the CORS layer: which origins may be echoed back in
+/// `Access-Control-Allow-Origin`, and what a preflight reply advertises.
+/// Origins are matched exactly and echoed back individually rather than
+/// answered with a blanket `*`, since that's required once credentials are
+/// involved.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_secs: u64,
+}
+
+impl CorsConfig {
+    pub fn new(allowed_origins: Vec<String>) -> Self {
+        CorsConfig {
+            allowed_origins,
+            allowed_methods: vec!["GET".to_string(), "POST".to_string(), "PUT".to_string(), "DELETE".to_string(), "PATCH".to_string(), "OPTIONS".to_string()],
+            allowed_headers: vec!["Content-Type".to_string(), "Authorization".to_string()],
+            max_age_secs: 86400,
+        }
+    }
+
+    /// Whether `origin` appears in the allowlist.
+    pub fn is_allowed_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == origin)
+    }
+
+    /// Builds the `204 No Content` reply to an `OPTIONS` preflight request,
+    /// echoing back the request's origin when it's on the allowlist.
+    pub fn preflight_response(&self, request: &HttpRequest) -> Response {
+        let mut response = Response::no_content();
+
+        if let Some(origin) = request.headers.get("origin").and_then(|v| str::from_utf8(v).ok()) {
+            if self.is_allowed_origin(origin) {
+                response.headers.insert("Access-Control-Allow-Origin".to_string(), origin.to_string());
+            }
+        }
+
+        response.headers.insert("Access-Control-Allow-Methods".to_string(), self.allowed_methods.join(", "));
+        response.headers.insert("Access-Control-Allow-Headers".to_string(), self.allowed_headers.join(", "));
+        response.headers.insert("Access-Control-Max-Age".to_string(), self.max_age_secs.to_string());
+        response
+    }
+}
+
 #[derive(Debug)]
 pub struct Response {
     pub status_code: u16,
@@ -278,6 +492,15 @@ impl Response {
         res
     }
 
+    /// Helper to create a standard `405 Method Not Allowed` response,
+    /// used when a route's path matches but not its method.
+    pub fn method_not_allowed() -> Self {
+        let body = "<h1>405 Method Not Allowed</h1>".as_bytes().to_vec();
+        let mut res = Response::new(405, "Method Not Allowed".to_string(), Some(body));
+        res.headers.insert("Content-Type".to_string(), "text/html".to_string());
+        res
+    }
+
     /// Helper to create a standard `400 Bad Request` response.
     pub fn bad_request() -> Self {
         let body = "<h1>400 Bad Request</h1>".as_bytes().to_vec();
@@ -286,6 +509,105 @@ impl Response {
         res
     }
 
+    /// Helper to create a `413 Payload Too Large` response, used when a
+    /// request exceeds the server's configured maximum size while still
+    /// being parsed incrementally.
+    pub fn payload_too_large() -> Self {
+        let body = "<h1>413 Payload Too Large</h1>".as_bytes().to_vec();
+        let mut res = Response::new(413, "Payload Too Large".to_string(), Some(body));
+        res.headers.insert("Content-Type".to_string(), "text/html".to_string());
+        res
+    }
+
+    /// Helper to create a `408 Request Timeout` response, used when a
+    /// client doesn't send (or finish) a request within the server's
+    /// configured read deadlines.
+    pub fn request_timeout() -> Self {
+        let body = "<h1>408 Request Timeout</h1>".as_bytes().to_vec();
+        let mut res = Response::new(408, "Request Timeout".to_string(), Some(body));
+        res.headers.insert("Content-Type".to_string(), "text/html".to_string());
+        res
+    }
+
+    /// Helper to create a standard `204 No Content` response, used for
+    /// successful CORS preflight replies among other bodiless responses.
+    pub fn no_content() -> Self {
+        Response::new(204, "No Content".to_string(), None)
+    }
+
+    /// Helper to create a standard `500 Internal Server Error` response,
+    /// used when a connection fails for reasons outside the client's
+    /// request (e.g. an I/O error).
+    pub fn internal_server_error() -> Self {
+        let body = "<h1>500 Internal Server Error</h1>".as_bytes().to_vec();
+        let mut res = Response::new(500, "Internal Server Error".to_string(), Some(body));
+        res.headers.insert("Content-Type".to_string(), "text/html".to_string());
+        res
+    }
+
+    /// Helper to create the `101 Switching Protocols` response that
+    /// completes a WebSocket handshake, carrying the computed
+    /// `Sec-WebSocket-Accept` value back to the client.
+    pub fn switching_protocols(sec_websocket_accept: String) -> Self {
+        let mut res = Response::new(101, "Switching Protocols".to_string(), None);
+        res.headers.insert("Upgrade".to_string(), "websocket".to_string());
+        res.headers.insert("Connection".to_string(), "Upgrade".to_string());
+        res.headers.insert("Sec-WebSocket-Accept".to_string(), sec_websocket_accept);
+        res
+    }
+
+    /// Compresses `self.body` in place using the best codec the client
+    /// advertises in its `Accept-Encoding` header (preferred order: br,
+    /// gzip, deflate), setting `Content-Encoding` to match. Bodies that are
+    /// missing, empty, below `MIN_COMPRESSION_LEN`, or already encoded are
+    /// left untouched, since compressing them isn't worth the CPU cost.
+    pub fn with_compression(&mut self, accept_encoding: &str) {
+        if self.headers.contains_key("Content-Encoding") {
+            return;
+        }
+
+        let Some(body) = self.body.as_ref() else { return };
+        if body.len() < MIN_COMPRESSION_LEN {
+            return;
+        }
+
+        let Some(encoding) = pick_encoding(accept_encoding) else { return };
+
+        let compressed = match encoding {
+            "br" => {
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                if writer.write_all(body).is_err() {
+                    return;
+                }
+                writer.into_inner()
+            }
+            "gzip" => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                if encoder.write_all(body).is_err() {
+                    return;
+                }
+                match encoder.finish() {
+                    Ok(bytes) => bytes,
+                    Err(_) => return,
+                }
+            }
+            "deflate" => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                if encoder.write_all(body).is_err() {
+                    return;
+                }
+                match encoder.finish() {
+                    Ok(bytes) => bytes,
+                    Err(_) => return,
+                }
+            }
+            _ => return,
+        };
+
+        self.body = Some(compressed);
+        self.headers.insert("Content-Encoding".to_string(), encoding.to_string());
+    }
+
     /// Serializes the Response struct into a `Vec<u8>` of raw HTTP response bytes.
     pub fn into_bytes(&self) -> Vec<u8> {
         // Start with the status line, e.g., "HTTP/1.1 200 OK\r\n"
@@ -299,9 +621,13 @@ impl Response {
             headers_str.push_str(&format!("{}: {}\r\n", name, value));
         }
         
-        // Automatically calculate and add the Content-Length header based on the body's size.
-        let content_length = self.body.as_ref().map_or(0, |b| b.len());
-        headers_str.push_str(&format!("Content-Length: {}\r\n", content_length));
+        // 1xx and 204 responses must not carry a body or a Content-Length
+        // header (RFC 7230 §3.3.2), so skip it for those statuses.
+        let omits_content_length = self.status_code < 200 || self.status_code == 204;
+        if !omits_content_length {
+            let content_length = self.body.as_ref().map_or(0, |b| b.len());
+            headers_str.push_str(&format!("Content-Length: {}\r\n", content_length));
+        }
 
         // Combine the status line, headers, the final CRLF, and the body.
         let mut response_bytes = Vec::new();
@@ -0,0 +1,106 @@
+// src/http/router.rs
+//
+// A small path-parameter router, replacing an ever-growing hardcoded
+// `match` on `(Method, path)`. Patterns are registered once via `add` and
+// segments beginning with `:` or wrapped in `{}` capture a value into the
+// dispatched `HttpRequest`'s `params` map.
+
+use std::collections::HashMap;
+
+use super::{HttpRequest, Method, Response};
+
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+struct Route {
+    method: Method,
+    segments: Vec<Segment>,
+    handler: Box<dyn Fn(HttpRequest) -> Response + Send + Sync>,
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router { routes: Vec::new() }
+    }
+
+    /// Registers `handler` for requests matching `method` and `pattern`,
+    /// e.g. `router.add(Method::Get, "/api/users/:id", handler)`. A
+    /// pattern segment written as `:name` or `{name}` captures that path
+    /// segment into `HttpRequest::params` under `"name"`.
+    pub fn add<F>(&mut self, method: Method, pattern: &str, handler: F)
+    where
+        F: Fn(HttpRequest) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            segments: Self::parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+    }
+
+    fn parse_pattern(pattern: &str) -> Vec<Segment> {
+        pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Segment::Param(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect()
+    }
+
+    /// Matches `request.path` segment-by-segment against every registered
+    /// route, captures parameters, and invokes the first handler whose
+    /// method and path both match. Falls back to `404 Not Found` when no
+    /// route's path matches, and `405 Method Not Allowed` when a route's
+    /// path matches but not its method.
+    pub fn dispatch(&self, mut request: HttpRequest) -> Response {
+        let path_segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut path_matches_exist = false;
+
+        for route in &self.routes {
+            if route.segments.len() != path_segments.len() {
+                continue;
+            }
+
+            let mut params = HashMap::new();
+            let path_matches = route.segments.iter().zip(path_segments.iter()).all(|(segment, actual)| match segment {
+                Segment::Literal(literal) => literal == actual,
+                Segment::Param(name) => {
+                    params.insert(name.clone(), actual.to_string());
+                    true
+                }
+            });
+
+            if !path_matches {
+                continue;
+            }
+            path_matches_exist = true;
+
+            if route.method != request.method {
+                continue;
+            }
+
+            request.params = params;
+            return (route.handler)(request);
+        }
+
+        if path_matches_exist {
+            Response::method_not_allowed()
+        } else {
+            Response::not_found()
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// src/http/websocket.rs
+//
+// RFC 6455 handshake and a minimal frame decoder. `upgrade_key` spots an
+// upgrade request and hands back the client's handshake key;
+// `accept_key` turns that into the `Sec-WebSocket-Accept` value.
+// `decode_frame` then parses whatever frames arrive once the raw
+// `TcpStream` has been handed off from the HTTP layer.
+
+use base64::Engine;
+use sha1::{Digest, Sha1};
+
+use super::{HttpRequest, Method};
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Returns the `Sec-WebSocket-Key` value if `request` is a valid WebSocket
+/// upgrade request (`GET` with `Upgrade: websocket` and `Connection:
+/// Upgrade`), or `None` otherwise.
+pub fn upgrade_key(request: &HttpRequest) -> Option<String> {
+    if request.method != Method::Get {
+        return None;
+    }
+
+    let upgrade = request.headers.get("upgrade")?;
+    if !std::str::from_utf8(upgrade).ok()?.eq_ignore_ascii_case("websocket") {
+        return None;
+    }
+
+    let connection = request.headers.get("connection")?;
+    let connection = std::str::from_utf8(connection).ok()?;
+    if !connection.split(',').any(|token| token.trim().eq_ignore_ascii_case("upgrade")) {
+        return None;
+    }
+
+    let key = request.headers.get("sec-websocket-key")?;
+    Some(std::str::from_utf8(key).ok()?.to_string())
+}
+
+/// Computes the `Sec-WebSocket-Accept` value for a client's
+/// `Sec-WebSocket-Key`, per RFC 6455 §1.3: SHA-1 of the key concatenated
+/// with the WebSocket GUID, base64-encoded.
+pub fn accept_key(client_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(client_key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(hasher.finalize())
+}
+
+/// The largest frame payload we're willing to allocate for, guarding
+/// against a client claiming an absurd 64-bit length.
+const MAX_FRAME_PAYLOAD: usize = 16 * 1024 * 1024; // 16 MiB
+
+#[derive(Debug, PartialEq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+    Other(u8),
+}
+
+impl Opcode {
+    fn from_u8(byte: u8) -> Self {
+        match byte {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xA => Opcode::Pong,
+            other => Opcode::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FrameError {
+    /// Not enough bytes have arrived yet to decode a full frame.
+    Partial,
+    /// The frame declares a payload larger than `MAX_FRAME_PAYLOAD`.
+    PayloadTooLarge,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: Opcode,
+    pub payload: Vec<u8>,
+}
+
+/// Decodes a single WebSocket frame from the front of `buffer`, mirroring
+/// `parse_request`'s shape: on success it returns the frame along with how
+/// many bytes it consumed, so callers can drain exactly that much and keep
+/// parsing any pipelined frames left in the buffer.
+pub fn decode_frame(buffer: &[u8]) -> Result<(Frame, usize), FrameError> {
+    if buffer.len() < 2 {
+        return Err(FrameError::Partial);
+    }
+
+    let fin = buffer[0] & 0b1000_0000 != 0;
+    let opcode = Opcode::from_u8(buffer[0] & 0b0000_1111);
+    let masked = buffer[1] & 0b1000_0000 != 0;
+    let mut payload_len = (buffer[1] & 0b0111_1111) as usize;
+
+    let mut cursor = 2;
+    if payload_len == 126 {
+        if buffer.len() < cursor + 2 {
+            return Err(FrameError::Partial);
+        }
+        payload_len = u16::from_be_bytes([buffer[cursor], buffer[cursor + 1]]) as usize;
+        cursor += 2;
+    } else if payload_len == 127 {
+        if buffer.len() < cursor + 8 {
+            return Err(FrameError::Partial);
+        }
+        let mut len_bytes = [0u8; 8];
+        len_bytes.copy_from_slice(&buffer[cursor..cursor + 8]);
+        let payload_len_64 = u64::from_be_bytes(len_bytes);
+        if payload_len_64 > MAX_FRAME_PAYLOAD as u64 {
+            return Err(FrameError::PayloadTooLarge);
+        }
+        payload_len = payload_len_64 as usize;
+        cursor += 8;
+    }
+
+    if payload_len > MAX_FRAME_PAYLOAD {
+        return Err(FrameError::PayloadTooLarge);
+    }
+
+    let mask_key = if masked {
+        if buffer.len() < cursor + 4 {
+            return Err(FrameError::Partial);
+        }
+        let key = [buffer[cursor], buffer[cursor + 1], buffer[cursor + 2], buffer[cursor + 3]];
+        cursor += 4;
+        Some(key)
+    } else {
+        None
+    };
+
+    if buffer.len() < cursor + payload_len {
+        return Err(FrameError::Partial);
+    }
+
+    let mut payload = buffer[cursor..cursor + payload_len].to_vec();
+    if let Some(key) = mask_key {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= key[i % 4];
+        }
+    }
+    cursor += payload_len;
+
+    Ok((Frame { fin, opcode, payload }, cursor))
+}
@@ -1,15 +1,20 @@
 // src/main.rs
 
+use std::str;
+use std::sync::Arc;
+
 // --- NEW IMPORTS ---
 // We now use Tokio's I/O types and traits.
 use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{timeout, Duration, Instant};
 
 // 1. Declare the http module (no change here)
 mod http;
 
 // 2. Import our HTTP types (no change here)
-use http::{HttpRequest, Method, Header, Response};
+use http::{CorsConfig, HttpRequest, Method, Header, Response, ResponseError, ServerError};
+use http::router::Router;
 
 /// The entry point of our server application.
 /// The `#[tokio::main]` macro sets up the asynchronous runtime.
@@ -19,6 +24,22 @@ async fn main() {
     let listener = TcpListener::bind("127.0.0.1:7878").await.expect("Failed to bind to address");
     println!("Async Server listening on http://127.0.0.1:7878");
 
+    let cors_config = Arc::new(CorsConfig::new(vec![
+        "http://localhost:3000".to_string(),
+        "http://127.0.0.1:3000".to_string(),
+    ]));
+
+    let mut router = Router::new();
+    router.add(Method::Get, "/", |_request| {
+        let body = "<h1>Welcome!</h1><p>This is the ASYNCHRONOUS Rusty Web server.</p>".as_bytes().to_vec();
+        Response::ok(body, "text/html")
+    });
+    router.add(Method::Get, "/api/message", |_request| {
+        let body = r#"{"framework":"Rusty Web","status":"async and awesome"}"#.as_bytes().to_vec();
+        Response::ok(body, "application/json")
+    });
+    let router = Arc::new(router);
+
     // The main server loop.
     loop {
         // Asynchronously wait for an inbound connection.
@@ -29,8 +50,10 @@ async fn main() {
                 // A new connection has been established.
                 // Spawn a new asynchronous task to handle this connection.
                 // The `move` keyword transfers ownership of the `stream` to the new task.
+                let cors_config = cors_config.clone();
+                let router = router.clone();
                 tokio::spawn(async move {
-                    handle_connection(stream).await;
+                    handle_connection(stream, cors_config, router).await;
                 });
             }
             Err(e) => {
@@ -41,41 +64,173 @@ async fn main() {
     }
 }
 
+/// The largest request (headers + body) we are willing to buffer before
+/// giving up and returning `413 Payload Too Large`. This keeps a slow or
+/// malicious client from growing `read_buffer` without bound.
+const MAX_REQUEST_SIZE: usize = 1024 * 1024; // 1 MiB
+
+/// How long we'll wait for a new request to begin arriving on an otherwise
+/// idle keep-alive connection before closing it.
+const KEEP_ALIVE_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long we'll wait for a request to finish once it has started
+/// arriving, regardless of keep-alive state. Shorter than the idle
+/// timeout since a client mid-request is expected to keep sending.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Handles a single connection. The function is now `async`.
 /// It takes a `tokio::net::TcpStream` instead of a `std::net::TcpStream`.
-async fn handle_connection(mut stream: TcpStream) {
-    let mut buffer = [0; 2048];
-    let mut headers = [Header { name: "", value: &[] }; 32];
-
-    // Asynchronously read data from the stream.
-    let bytes_read = match stream.read(&mut buffer).await {
-        Ok(0) => { println!("Client disconnected gracefully."); return; },
-        Ok(n) => n,
-        Err(e) => { eprintln!("Failed to read from stream: {}", e); return; }
-    };
-
-    println!("Received {} bytes of data.", bytes_read);
-
-    let response = match http::parse_request(&buffer[..bytes_read], &mut headers) {
-        Ok((borrowed_request, _)) => {
-            match HttpRequest::try_from(borrowed_request) {
-                Ok(request) => add_cors_headers(request),
-                Err(e) => {
-                    eprintln!("Failed to process request: {:?}", e);
-                    Response::bad_request()
+///
+/// Loops over multiple requests on the same socket when the client and the
+/// HTTP version agree on keep-alive, only returning once the connection
+/// should close.
+async fn handle_connection(mut stream: TcpStream, cors_config: Arc<CorsConfig>, router: Arc<Router>) {
+    let mut read_buffer: Vec<u8> = Vec::new();
+
+    loop {
+        let (request, keep_alive, consumed) = match read_request(&mut stream, &mut read_buffer).await {
+            Ok(None) => { println!("Client disconnected before completing a request."); return; }
+            Ok(Some(parsed)) => parsed,
+            Err(e) => {
+                eprintln!("Connection error: {:?}", e);
+                let response = e.status_response();
+                stream.write_all(&response.into_bytes()).await.unwrap_or_else(|e| eprintln!("Failed to write response: {}", e));
+                stream.flush().await.unwrap_or_else(|e| eprintln!("Failed to flush stream: {}", e));
+                return;
+            }
+        };
+
+        if let Some(client_key) = http::websocket::upgrade_key(&request) {
+            let accept = http::websocket::accept_key(&client_key);
+            let response = Response::switching_protocols(accept);
+            stream.write_all(&response.into_bytes()).await.unwrap_or_else(|e| eprintln!("Failed to write response: {}", e));
+            stream.flush().await.unwrap_or_else(|e| eprintln!("Failed to flush stream: {}", e));
+            // Anything past the upgrade request in `read_buffer` is already
+            // the start of a WebSocket frame (pipelined by a fast or
+            // non-conformant client); hand it off instead of discarding it.
+            let leftover = read_buffer.split_off(consumed);
+            handle_websocket(stream, leftover).await;
+            return;
+        }
+
+        let response = negotiate_compression(request, &cors_config, &router);
+
+        // Asynchronously write the final, serialized response to the stream.
+        stream.write_all(&response.into_bytes()).await.unwrap_or_else(|e| eprintln!("Failed to write response: {}", e));
+        // Asynchronously flush the stream.
+        stream.flush().await.unwrap_or_else(|e| eprintln!("Failed to flush stream: {}", e));
+
+        if !keep_alive {
+            return;
+        }
+
+        // Drop the bytes belonging to the request we just served, keeping
+        // any pipelined bytes for the next iteration of the loop.
+        read_buffer.drain(..consumed);
+    }
+}
+
+/// Reads and parses a single request off `stream`, growing `read_buffer`
+/// until `parse_request` succeeds, the connection closes, or the request
+/// exceeds `MAX_REQUEST_SIZE`. `ParseError::Partial` means "not enough
+/// bytes yet", so it drives this loop rather than being treated as a hard
+/// failure; every other outcome is reported through `ServerError` so the
+/// caller can convert it into the right status response with one match.
+///
+/// Returns `Ok(None)` when the client closes the connection before a
+/// request completes, which isn't an error worth responding to.
+async fn read_request(
+    stream: &mut TcpStream,
+    read_buffer: &mut Vec<u8>,
+) -> Result<Option<(HttpRequest, bool, usize)>, ServerError> {
+    let mut chunk = [0; 2048];
+    // Fixed once the request has started arriving, so the slow-request
+    // deadline bounds the whole request rather than each individual read.
+    let mut request_deadline: Option<Instant> = None;
+
+    loop {
+        let mut headers = [Header { name: "", value: &[] }; 32];
+        match http::parse_request(read_buffer, &mut headers) {
+            Ok((borrowed_request, total_request_size)) => {
+                let keep_alive = should_keep_alive(&borrowed_request);
+                let request = HttpRequest::try_from(borrowed_request)?;
+                return Ok(Some((request, keep_alive, total_request_size)));
+            }
+            Err(http::ParseError::Partial) => {
+                if read_buffer.len() >= MAX_REQUEST_SIZE {
+                    return Err(ServerError::TooLarge);
+                }
+
+                // An empty buffer means we're waiting for a brand new
+                // request (idle window); any bytes already buffered mean
+                // one is mid-flight (slow-request deadline).
+                let deadline = if read_buffer.is_empty() {
+                    KEEP_ALIVE_IDLE_TIMEOUT
+                } else {
+                    let request_deadline = *request_deadline.get_or_insert_with(|| Instant::now() + REQUEST_READ_TIMEOUT);
+                    request_deadline.saturating_duration_since(Instant::now())
+                };
+
+                match timeout(deadline, stream.read(&mut chunk)).await {
+                    Ok(Ok(0)) => return Ok(None),
+                    Ok(Ok(n)) => read_buffer.extend_from_slice(&chunk[..n]),
+                    Ok(Err(e)) => return Err(e.into()),
+                    Err(_) => return Err(ServerError::Timeout),
                 }
             }
+            Err(e) => return Err(e.into()),
         }
-        Err(e) => {
-            eprintln!("Failed to parse request: {:?}", e);
-            Response::bad_request()
+    }
+}
+
+/// Decides whether a connection should stay open for another request based
+/// on the `Connection` header and the HTTP version's default (HTTP/1.1
+/// defaults to keep-alive, HTTP/1.0 defaults to close).
+fn should_keep_alive(request: &http::Request) -> bool {
+    for header in request.headers {
+        if header.name.eq_ignore_ascii_case("connection") {
+            return match std::str::from_utf8(header.value) {
+                Ok(value) => value.trim().eq_ignore_ascii_case("keep-alive"),
+                Err(_) => false,
+            };
         }
-    };
+    }
 
-    // Asynchronously write the final, serialized response to the stream.
-    stream.write_all(&response.into_bytes()).await.unwrap_or_else(|e| eprintln!("Failed to write response: {}", e));
-    // Asynchronously flush the stream.
-    stream.flush().await.unwrap_or_else(|e| eprintln!("Failed to flush stream: {}", e));
+    request.version.eq_ignore_ascii_case("HTTP/1.1")
+}
+
+/// Takes over a raw `TcpStream` after a successful WebSocket handshake and
+/// decodes frames from it until the client closes the connection.
+/// `buffer` seeds the decode buffer with any bytes that arrived
+/// past the upgrade request before more are read from the socket.
+async fn handle_websocket(mut stream: TcpStream, mut buffer: Vec<u8>) {
+    let mut chunk = [0; 2048];
+
+    loop {
+        match http::websocket::decode_frame(&buffer) {
+            Ok((frame, consumed)) => {
+                buffer.drain(..consumed);
+                match frame.opcode {
+                    http::websocket::Opcode::Close => {
+                        println!("WebSocket connection closed by client.");
+                        return;
+                    }
+                    _ => println!("Received WebSocket frame: {:?} ({} bytes, fin={})", frame.opcode, frame.payload.len(), frame.fin),
+                }
+            }
+            Err(http::websocket::FrameError::Partial) => {
+                match stream.read(&mut chunk).await {
+                    Ok(0) => { println!("WebSocket client disconnected."); return; }
+                    Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                    Err(e) => { eprintln!("Failed to read WebSocket frame: {}", e); return; }
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to decode WebSocket frame: {:?}", e);
+                return;
+            }
+        }
+    }
 }
 
 // --- NO CHANGES BELOW THIS LINE ---
@@ -83,32 +238,50 @@ async fn handle_connection(mut stream: TcpStream) {
 
 // --- MIDDLEWARE LAYER ---
 
-fn add_cors_headers(request: HttpRequest) -> Response {
-    let mut response = log_request(request);
-    response.headers.insert("Access-Control-Allow-Origin".to_string(), "*".to_string());
+/// Negotiates response compression based on the request's
+/// `Accept-Encoding` header, so individual routes don't have to.
+fn negotiate_compression(request: HttpRequest, cors_config: &CorsConfig, router: &Router) -> Response {
+    let accept_encoding = request.headers.get("accept-encoding")
+        .and_then(|v| str::from_utf8(v).ok())
+        .unwrap_or("")
+        .to_string();
+
+    let mut response = apply_cors(request, cors_config, router);
+    response.with_compression(&accept_encoding);
     response
 }
 
-fn log_request(request: HttpRequest) -> Response {
-    let method = request.method.clone();
-    let path = request.path.clone();
-    let response = route_request(request);
-    println!("-> Request: {:?} {} -> Response: {} {}", method, path, response.status_code, response.status_text);
+/// Applies the CORS layer. `OPTIONS` preflight requests are answered
+/// directly and never reach the router; other requests are routed as
+/// normal and have `Access-Control-Allow-Origin` echoed back onto the
+/// response when their `Origin` is on the allowlist.
+fn apply_cors(request: HttpRequest, cors_config: &CorsConfig, router: &Router) -> Response {
+    if is_preflight_request(&request) {
+        return cors_config.preflight_response(&request);
+    }
+
+    let allowed_origin = request.headers.get("origin")
+        .and_then(|v| str::from_utf8(v).ok())
+        .filter(|origin| cors_config.is_allowed_origin(origin))
+        .map(|origin| origin.to_string());
+
+    let mut response = log_request(request, router);
+    if let Some(origin) = allowed_origin {
+        response.headers.insert("Access-Control-Allow-Origin".to_string(), origin);
+    }
     response
 }
 
-// --- ROUTER / HANDLER LAYER ---
+/// An `OPTIONS` request carrying `Access-Control-Request-Method` is a CORS
+/// preflight and must be short-circuited before routing.
+fn is_preflight_request(request: &HttpRequest) -> bool {
+    request.method == Method::Options && request.headers.contains_key("access-control-request-method")
+}
 
-fn route_request(request: HttpRequest) -> Response {
-    match (&request.method, request.path.as_str()) {
-        (Method::Get, "/") => {
-            let body = "<h1>Welcome!</h1><p>This is the ASYNCHRONOUS Rusty Web server.</p>".as_bytes().to_vec();
-            Response::ok(body, "text/html")
-        }
-        (Method::Get, "/api/message") => {
-            let body = r#"{"framework":"Rusty Web","status":"async and awesome"}"#.as_bytes().to_vec();
-            Response::ok(body, "application/json")
-        }
-        _ => Response::not_found(),
-    }
+fn log_request(request: HttpRequest, router: &Router) -> Response {
+    let method = request.method.clone();
+    let path = request.path.clone();
+    let response = router.dispatch(request);
+    println!("-> Request: {:?} {} -> Response: {} {}", method, path, response.status_code, response.status_text);
+    response
 }
\ No newline at end of file